@@ -0,0 +1,167 @@
+//! Core authorization path: turns principal/action/resource/policy/entities/
+//! context strings into a Cedar decision, returning a `Result` instead of
+//! panicking so this crate is usable as an embedded library, not just a
+//! one-shot CLI.
+
+use cedar_policy::{Authorizer, Context, Entities, EntityUid, PolicySet, Request};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Every parse step in the authorization path that can fail, each carrying
+/// the offending input and the underlying Cedar error.
+#[derive(Debug)]
+pub enum AuthzError {
+    Principal { input: String, source: String },
+    Action { input: String, source: String },
+    Resource { input: String, source: String },
+    Context { input: String, source: String },
+    Request { source: String },
+    Policy { input: String, source: String },
+    Entities { input: String, source: String },
+}
+
+impl fmt::Display for AuthzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthzError::Principal { input, source } => write!(f, "failed to parse principal {input:?}: {source}"),
+            AuthzError::Action { input, source } => write!(f, "failed to parse action {input:?}: {source}"),
+            AuthzError::Resource { input, source } => write!(f, "failed to parse resource {input:?}: {source}"),
+            AuthzError::Context { input, source } => write!(f, "failed to parse context {input:?}: {source}"),
+            AuthzError::Request { source } => write!(f, "failed to build request: {source}"),
+            AuthzError::Policy { input, source } => write!(f, "failed to parse policies {input:?}: {source}"),
+            AuthzError::Entities { input, source } => write!(f, "failed to parse entities {input:?}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for AuthzError {}
+
+/// Why a request was permitted/denied: the policy ids Cedar's `Diagnostics`
+/// names as `reason`, plus any evaluation `errors` (e.g. a policy that was
+/// skipped because it referenced a missing entity attribute).
+#[derive(Serialize)]
+pub struct AuthzDiagnostics {
+    pub determining_policies: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TimingOutput {
+    pub parse_policy_us: u128,
+    pub parse_context_us: u128,
+    pub parse_entities_us: u128,
+    pub build_request_us: u128,
+    pub authorization_us: u128,
+    pub total_us: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_hit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_lookup_us: Option<u128>,
+}
+
+/// The full result of an authorization: the decision, why Cedar reached
+/// it, and where the time went.
+#[derive(Serialize)]
+pub struct AuthzOutcome {
+    pub decision: String,
+    pub diagnostics: AuthzDiagnostics,
+    pub timing: TimingOutput,
+}
+
+/// Authorize a request using Cedar Policy with timing. Returns `Err` on any
+/// parse failure instead of panicking, so callers (CLI, cache, serve) can
+/// report a structured error and recover.
+///
+/// Parses `policies`/`entities` fresh on every call; a caller that already
+/// has a parsed `PolicySet`/`Entities` for many requests (`batch::run_batch`)
+/// should use `authorize_parsed` instead to avoid re-parsing per request.
+pub fn authorize_with_timing(
+    principal: &str,
+    action: &str,
+    resource: &str,
+    policies: &str,
+    entities: &str,
+    context: Option<&str>,
+) -> Result<AuthzOutcome, AuthzError> {
+    let policy_start = Instant::now();
+    let policy_set = PolicySet::from_str(policies)
+        .map_err(|err| AuthzError::Policy { input: policies.to_string(), source: err.to_string() })?;
+    let parse_policy_us = policy_start.elapsed().as_micros();
+
+    let entities_start = Instant::now();
+    let parsed_entities = Entities::from_json_str(entities, None)
+        .map_err(|err| AuthzError::Entities { input: entities.to_string(), source: err.to_string() })?;
+    let parse_entities_us = entities_start.elapsed().as_micros();
+
+    let mut outcome = authorize_parsed(principal, action, resource, &policy_set, &parsed_entities, context)?;
+    outcome.timing.parse_policy_us = parse_policy_us;
+    outcome.timing.parse_entities_us = parse_entities_us;
+    outcome.timing.total_us += parse_policy_us + parse_entities_us;
+    Ok(outcome)
+}
+
+/// Authorizes a request against an already-parsed `PolicySet`/`Entities`,
+/// with timing for everything but the (zeroed) policy/entity parse steps.
+/// Returns `Err` instead of panicking, same as `authorize_with_timing`.
+pub fn authorize_parsed(
+    principal: &str,
+    action: &str,
+    resource: &str,
+    policy_set: &PolicySet,
+    entities: &Entities,
+    context: Option<&str>,
+) -> Result<AuthzOutcome, AuthzError> {
+    let total_start = Instant::now();
+
+    let principal_uid = EntityUid::from_str(principal)
+        .map_err(|err| AuthzError::Principal { input: principal.to_string(), source: err.to_string() })?;
+    let action_uid = EntityUid::from_str(action)
+        .map_err(|err| AuthzError::Action { input: action.to_string(), source: err.to_string() })?;
+    let resource_uid = EntityUid::from_str(resource)
+        .map_err(|err| AuthzError::Resource { input: resource.to_string(), source: err.to_string() })?;
+
+    // Parse context
+    let context_start = Instant::now();
+    let context_str = context.unwrap_or("{}");
+    let context_json: serde_json::Value = serde_json::from_str(context_str)
+        .map_err(|err| AuthzError::Context { input: context_str.to_string(), source: err.to_string() })?;
+    let context = Context::from_json_value(context_json, None)
+        .map_err(|err| AuthzError::Context { input: context_str.to_string(), source: err.to_string() })?;
+    let parse_context_us = context_start.elapsed().as_micros();
+
+    // Build request
+    let request_start = Instant::now();
+    let request = Request::new(principal_uid, action_uid, resource_uid, context, None)
+        .map_err(|err| AuthzError::Request { source: err.to_string() })?;
+    let build_request_us = request_start.elapsed().as_micros();
+
+    // Authorization
+    let auth_start = Instant::now();
+    let authorizer = Authorizer::new();
+    let response = authorizer.is_authorized(&request, policy_set, entities);
+    let authorization_us = auth_start.elapsed().as_micros();
+
+    let total_us = total_start.elapsed().as_micros();
+
+    let diagnostics = AuthzDiagnostics {
+        determining_policies: response.diagnostics().reason().map(|id| id.to_string()).collect(),
+        errors: response.diagnostics().errors().map(|err| err.to_string()).collect(),
+    };
+
+    Ok(AuthzOutcome {
+        decision: format!("{:?}", response.decision()),
+        diagnostics,
+        timing: TimingOutput {
+            parse_policy_us: 0,
+            parse_context_us,
+            parse_entities_us: 0,
+            build_request_us,
+            authorization_us,
+            total_us,
+            cache_hit: None,
+            cache_lookup_us: None,
+        },
+    })
+}
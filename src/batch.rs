@@ -0,0 +1,375 @@
+//! Batch authorization: evaluates many requests against a single parsed
+//! `PolicySet`/`Entities`, for bulk replay or audit of MCP access logs.
+//!
+//! The input file is either newline-delimited JSON or a single JSON array
+//! of `BatchEntry` objects. Each entry supplies only the fields that vary;
+//! large shared context values can be referenced by id through a
+//! `contexts` table instead of being repeated per-entry, and a `context_ref`
+//! can layer a few `context_overrides` on top of the shared base.
+//!
+//! The `contexts` table can be supplied three ways:
+//! - the whole file is a single JSON object with top-level `contexts` and
+//!   `requests` keys (`BatchFileRaw`);
+//! - in an NDJSON file, any line shaped `{"contexts": {...}}` merges into
+//!   the shared table instead of being treated as a request;
+//! - in a JSON-array file, any array element shaped the same way does the
+//!   same. A file can mix any number of these lines/elements with entries.
+//!
+//! A single malformed or invalid line must not take down a replay of a
+//! multi-thousand-line access log: every per-entry failure (bad JSON, a bad
+//! principal/action/resource, a missing `context_ref`) is reported as that
+//! one entry's `BatchResult`, via `authorize::authorize_parsed` — the same
+//! authorization path `authorize_with_timing` uses, minus the per-call
+//! policy/entity parsing this module already does once up front — rather
+//! than panicking.
+
+use crate::authorize::{authorize_parsed, AuthzDiagnostics};
+use cedar_policy::{Entities, PolicySet};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// A shared-contexts table keyed by id, referenced from entries via
+/// `context_ref` to keep the payload small when many requests share most
+/// of their context.
+#[derive(Deserialize, Default)]
+struct BatchFileRaw {
+    #[serde(default)]
+    contexts: HashMap<String, Value>,
+    requests: Vec<BatchEntry>,
+}
+
+/// A standalone `{"contexts": {...}}` line/element, merged into the shared
+/// table rather than treated as a request — distinguishable from a
+/// `BatchEntry` because it has no `principal`/`action`/`resource`.
+#[derive(Deserialize)]
+struct ContextsLine {
+    contexts: HashMap<String, Value>,
+}
+
+struct BatchFile {
+    contexts: HashMap<String, Value>,
+    requests: Vec<RawRequest>,
+}
+
+/// One line of NDJSON input, before it's known whether it parsed: a line
+/// that isn't valid JSON still needs to produce a `BatchResult`, not abort
+/// the whole batch.
+enum RawRequest {
+    Entry(BatchEntry),
+    Invalid(String),
+}
+
+#[derive(Deserialize)]
+struct BatchEntry {
+    principal: String,
+    action: String,
+    resource: String,
+    #[serde(default)]
+    context: Option<Value>,
+    #[serde(default)]
+    context_ref: Option<String>,
+    #[serde(default)]
+    context_overrides: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<AuthzDiagnostics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    fn error(principal: Option<String>, action: Option<String>, resource: Option<String>, error: String) -> BatchResult {
+        BatchResult { principal, action, resource, decision: None, diagnostics: None, error: Some(error) }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchTiming {
+    pub total_us: u128,
+    pub min_authorization_us: u128,
+    pub max_authorization_us: u128,
+    pub p50_authorization_us: u128,
+    pub p99_authorization_us: u128,
+}
+
+/// Parses the `--batch` file: either a JSON array of entries, or
+/// newline-delimited JSON objects, with an optional leading `contexts`
+/// table when the file is a single JSON object instead of an array (see
+/// the module doc for all three ways `contexts` can be supplied).
+///
+/// Only the file-level shape (not valid JSON at all, or a top-level array
+/// that doesn't deserialize as a list of entries/contexts lines) fails the
+/// whole parse; a single bad NDJSON line becomes a `RawRequest::Invalid`
+/// that `run_batch` turns into a failed result for that line alone.
+fn parse_batch_file(raw: &str) -> Result<BatchFile, String> {
+    let trimmed = raw.trim_start();
+    if trimmed.starts_with('[') {
+        let values: Vec<Value> = serde_json::from_str(trimmed).map_err(|err| format!("failed to parse batch array: {err}"))?;
+        let mut contexts = HashMap::new();
+        let mut requests = Vec::with_capacity(values.len());
+        for value in values {
+            if let Ok(contexts_line) = serde_json::from_value::<ContextsLine>(value.clone()) {
+                contexts.extend(contexts_line.contexts);
+                continue;
+            }
+            let entry: BatchEntry =
+                serde_json::from_value(value).map_err(|err| format!("failed to parse batch array entry: {err}"))?;
+            requests.push(RawRequest::Entry(entry));
+        }
+        return Ok(BatchFile { contexts, requests });
+    }
+    if trimmed.starts_with('{') {
+        if let Ok(file) = serde_json::from_str::<BatchFileRaw>(trimmed) {
+            return Ok(BatchFile {
+                contexts: file.contexts,
+                requests: file.requests.into_iter().map(RawRequest::Entry).collect(),
+            });
+        }
+    }
+
+    let mut contexts = HashMap::new();
+    let mut requests = Vec::new();
+    for line in trimmed.lines().filter(|line| !line.trim().is_empty()) {
+        if let Ok(contexts_line) = serde_json::from_str::<ContextsLine>(line) {
+            contexts.extend(contexts_line.contexts);
+            continue;
+        }
+        requests.push(match serde_json::from_str::<BatchEntry>(line) {
+            Ok(entry) => RawRequest::Entry(entry),
+            Err(err) => RawRequest::Invalid(format!("failed to parse batch line: {err}")),
+        });
+    }
+    Ok(BatchFile { contexts, requests })
+}
+
+fn resolve_context(entry: &BatchEntry, contexts: &HashMap<String, Value>) -> Result<Value, String> {
+    let mut context = match (&entry.context, &entry.context_ref) {
+        (Some(inline), _) => inline.clone(),
+        (None, Some(context_ref)) => contexts
+            .get(context_ref)
+            .cloned()
+            .ok_or_else(|| format!("context_ref {context_ref:?} not found in contexts table"))?,
+        (None, None) => Value::Object(Map::new()),
+    };
+
+    if let Some(overrides) = &entry.context_overrides {
+        let map = context.as_object_mut().ok_or_else(|| "context must be a JSON object to apply overrides".to_string())?;
+        for (key, value) in overrides {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+
+    Ok(context)
+}
+
+fn run_entry(entry: &BatchEntry, contexts: &HashMap<String, Value>, policy_set: &PolicySet, entities: &Entities) -> BatchResult {
+    let principal = Some(entry.principal.clone());
+    let action = Some(entry.action.clone());
+    let resource = Some(entry.resource.clone());
+
+    let context = match resolve_context(entry, contexts) {
+        Ok(context) => context,
+        Err(err) => return BatchResult::error(principal, action, resource, err),
+    };
+    let context_str = match serde_json::to_string(&context) {
+        Ok(context_str) => context_str,
+        Err(err) => return BatchResult::error(principal, action, resource, err.to_string()),
+    };
+
+    match authorize_parsed(&entry.principal, &entry.action, &entry.resource, policy_set, entities, Some(&context_str)) {
+        Ok(outcome) => BatchResult {
+            principal,
+            action,
+            resource,
+            decision: Some(outcome.decision),
+            diagnostics: Some(outcome.diagnostics),
+            error: None,
+        },
+        Err(err) => BatchResult::error(principal, action, resource, err.to_string()),
+    }
+}
+
+/// Runs every request in `batch_path` against `policies`/`entities`, parsed
+/// once, and returns one result per request plus aggregate timing. A
+/// malformed line or an invalid principal/action/resource/context produces
+/// a failed `BatchResult` for that line; it never aborts the rest of the
+/// run.
+pub fn run_batch(batch_path: &str, policies: &str, entities: &str) -> Result<(Vec<BatchResult>, BatchTiming), String> {
+    let total_start = Instant::now();
+
+    let raw = std::fs::read_to_string(batch_path).map_err(|err| format!("failed to read batch file {batch_path:?}: {err}"))?;
+    let batch = parse_batch_file(&raw)?;
+
+    let policy_set = PolicySet::from_str(policies).map_err(|err| format!("failed to parse policies: {err}"))?;
+    let entities = Entities::from_json_str(entities, None).map_err(|err| format!("failed to parse entities: {err}"))?;
+
+    let mut results = Vec::with_capacity(batch.requests.len());
+    let mut authorization_us_samples = Vec::with_capacity(batch.requests.len());
+
+    for entry in &batch.requests {
+        let entry_start = Instant::now();
+        let result = match entry {
+            RawRequest::Entry(entry) => run_entry(entry, &batch.contexts, &policy_set, &entities),
+            RawRequest::Invalid(err) => BatchResult::error(None, None, None, err.clone()),
+        };
+        authorization_us_samples.push(entry_start.elapsed().as_micros());
+        results.push(result);
+    }
+
+    authorization_us_samples.sort_unstable();
+    let timing = BatchTiming {
+        total_us: total_start.elapsed().as_micros(),
+        min_authorization_us: percentile(&authorization_us_samples, 0.0),
+        max_authorization_us: percentile(&authorization_us_samples, 1.0),
+        p50_authorization_us: percentile(&authorization_us_samples, 0.50),
+        p99_authorization_us: percentile(&authorization_us_samples, 0.99),
+    };
+
+    Ok((results, timing))
+}
+
+fn percentile(sorted_samples: &[u128], p: f64) -> u128 {
+    if sorted_samples.is_empty() {
+        return 0;
+    }
+    let index = ((sorted_samples.len() - 1) as f64 * p).round() as usize;
+    sorted_samples[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(context: Option<Value>, context_ref: Option<&str>, overrides: Option<Map<String, Value>>) -> BatchEntry {
+        BatchEntry {
+            principal: "User::\"alice\"".to_string(),
+            action: "Action::\"view\"".to_string(),
+            resource: "Photo::\"flower.jpg\"".to_string(),
+            context,
+            context_ref: context_ref.map(str::to_string),
+            context_overrides: overrides,
+        }
+    }
+
+    #[test]
+    fn inline_context_wins_over_context_ref() {
+        let inline = serde_json::json!({"source": "inline"});
+        let e = entry(Some(inline.clone()), Some("shared"), None);
+        let mut contexts = HashMap::new();
+        contexts.insert("shared".to_string(), serde_json::json!({"source": "shared"}));
+
+        assert_eq!(resolve_context(&e, &contexts).unwrap(), inline);
+    }
+
+    #[test]
+    fn context_ref_resolves_from_shared_table() {
+        let e = entry(None, Some("shared"), None);
+        let mut contexts = HashMap::new();
+        contexts.insert("shared".to_string(), serde_json::json!({"mfa_authenticated": true}));
+
+        assert_eq!(resolve_context(&e, &contexts).unwrap(), serde_json::json!({"mfa_authenticated": true}));
+    }
+
+    #[test]
+    fn missing_context_and_ref_default_to_empty_object() {
+        let e = entry(None, None, None);
+        assert_eq!(resolve_context(&e, &HashMap::new()).unwrap(), Value::Object(Map::new()));
+    }
+
+    #[test]
+    fn overrides_layer_on_top_of_the_base_context() {
+        let mut overrides = Map::new();
+        overrides.insert("mfa_authenticated".to_string(), Value::Bool(true));
+        let e = entry(None, Some("shared"), Some(overrides));
+        let mut contexts = HashMap::new();
+        contexts.insert(
+            "shared".to_string(),
+            serde_json::json!({"mfa_authenticated": false, "request_client_ip": "1.2.3.4"}),
+        );
+
+        let resolved = resolve_context(&e, &contexts).unwrap();
+        assert_eq!(
+            resolved,
+            serde_json::json!({"mfa_authenticated": true, "request_client_ip": "1.2.3.4"})
+        );
+    }
+
+    #[test]
+    fn missing_context_ref_is_an_error_not_a_panic() {
+        let e = entry(None, Some("missing"), None);
+        assert!(resolve_context(&e, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn overrides_on_a_non_object_context_is_an_error_not_a_panic() {
+        let mut overrides = Map::new();
+        overrides.insert("mfa_authenticated".to_string(), Value::Bool(true));
+        let e = entry(Some(Value::String("not an object".to_string())), None, Some(overrides));
+        assert!(resolve_context(&e, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn a_malformed_ndjson_line_produces_one_failed_result_not_a_parse_abort() {
+        let batch = parse_batch_file("{\"principal\": \"User::\\\"alice\\\"\"\nnot json at all").unwrap();
+        assert_eq!(batch.requests.len(), 2);
+        assert!(matches!(batch.requests[0], RawRequest::Invalid(_)));
+    }
+
+    #[test]
+    fn ndjson_contexts_line_populates_the_shared_table_for_later_entries() {
+        let raw = "{\"contexts\": {\"shared\": {\"mfa_authenticated\": true}}}\n\
+                   {\"principal\": \"User::\\\"alice\\\"\", \"action\": \"Action::\\\"view\\\"\", \
+                   \"resource\": \"Photo::\\\"flower.jpg\\\"\", \"context_ref\": \"shared\"}";
+        let batch = parse_batch_file(raw).unwrap();
+
+        assert_eq!(batch.requests.len(), 1);
+        let RawRequest::Entry(entry) = &batch.requests[0] else { panic!("expected an entry") };
+        assert_eq!(resolve_context(entry, &batch.contexts).unwrap(), serde_json::json!({"mfa_authenticated": true}));
+    }
+
+    #[test]
+    fn array_contexts_element_populates_the_shared_table_for_later_entries() {
+        let raw = serde_json::json!([
+            {"contexts": {"shared": {"mfa_authenticated": true}}},
+            {
+                "principal": "User::\"alice\"",
+                "action": "Action::\"view\"",
+                "resource": "Photo::\"flower.jpg\"",
+                "context_ref": "shared",
+            },
+        ])
+        .to_string();
+        let batch = parse_batch_file(&raw).unwrap();
+
+        assert_eq!(batch.requests.len(), 1);
+        let RawRequest::Entry(entry) = &batch.requests[0] else { panic!("expected an entry") };
+        assert_eq!(resolve_context(entry, &batch.contexts).unwrap(), serde_json::json!({"mfa_authenticated": true}));
+    }
+
+    #[test]
+    fn percentile_of_empty_samples_is_zero() {
+        assert_eq!(percentile(&[], 0.5), 0);
+    }
+
+    #[test]
+    fn percentile_picks_expected_index() {
+        let samples = vec![10, 20, 30, 40, 50];
+        assert_eq!(percentile(&samples, 0.0), 10);
+        assert_eq!(percentile(&samples, 1.0), 50);
+        assert_eq!(percentile(&samples, 0.5), 30);
+    }
+}
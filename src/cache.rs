@@ -0,0 +1,200 @@
+//! Optional Redis-backed cache for authorization decisions.
+//!
+//! The crate is named "stateful", but until now kept no state between calls
+//! — every request re-ran the full `Authorizer` path even when an identical
+//! request had just been answered. This module lets MCP servers that
+//! re-authorize the same tool calls repeatedly skip straight to a cached
+//! decision.
+//!
+//! Keys are namespaced by a content hash of the policy set, so swapping
+//! policies invalidates the whole cache for free instead of requiring an
+//! explicit flush.
+
+use redis::Connection;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A cached decision plus the policy ids and evaluation errors that
+/// produced it, mirroring what `Diagnostics::reason`/`errors` report, so a
+/// cache hit and a cache miss carry the same information.
+#[derive(Clone)]
+pub struct CachedDecision {
+    pub decision: String,
+    pub determining_policies: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Timing for a single cache lookup, merged into `TimingOutput` alongside
+/// `cache_hit`.
+pub struct CacheLookup {
+    pub hit: bool,
+    pub cache_lookup_us: u128,
+}
+
+/// Connects to Redis at `cache_url` and scopes all keys under a prefix
+/// derived from `policies` and `entities`, so a changed policy set *or* a
+/// changed entities file can't return a stale decision.
+pub struct DecisionCache {
+    conn: Mutex<Connection>,
+    ttl_seconds: u64,
+    key_prefix: String,
+}
+
+impl DecisionCache {
+    /// Connects to Redis, or returns `None` if it can't be reached. The
+    /// cache is strictly an optimization — a deployment with no Redis, or
+    /// one that's temporarily down, must fall back to the normal
+    /// `Authorizer` path rather than refuse to authorize at all.
+    pub fn connect(cache_url: &str, ttl_seconds: u64, policies: &str, entities: &str) -> Option<DecisionCache> {
+        let client = match redis::Client::open(cache_url) {
+            Ok(client) => client,
+            Err(err) => {
+                eprintln!("decision cache disabled: failed to create Redis client: {err}");
+                return None;
+            }
+        };
+        let conn = match client.get_connection() {
+            Ok(conn) => conn,
+            Err(err) => {
+                eprintln!("decision cache disabled: failed to connect to Redis: {err}");
+                return None;
+            }
+        };
+        let key_prefix = format!(
+            "cedar-authorize:{:x}:{:x}",
+            Sha256::digest(policies.as_bytes()),
+            Sha256::digest(entities.as_bytes()),
+        );
+        Some(DecisionCache { conn: Mutex::new(conn), ttl_seconds, key_prefix })
+    }
+
+    /// Returns `None` for a context that isn't valid JSON, so an invalid
+    /// `--context` fails the same way whether or not `--cache-url` is set
+    /// (a clean `AuthzError::Context` from `authorize_with_timing`), instead
+    /// of panicking here before that validation ever runs.
+    fn key(&self, principal: &str, action: &str, resource: &str, context: &str) -> Option<String> {
+        let canonical = format!("{principal}|{action}|{resource}|{}", canonicalize_json(context)?);
+        Some(format!("{}:{:x}", self.key_prefix, Sha256::digest(canonical.as_bytes())))
+    }
+
+    /// Looks up a cached decision for this request. Returns `None` on a
+    /// cache miss — including a Redis error, a corrupt entry, or a context
+    /// that isn't valid JSON — so the caller always falls back to running
+    /// the normal `Authorizer` path instead of failing the request.
+    pub fn get(&self, principal: &str, action: &str, resource: &str, context: &str) -> (Option<CachedDecision>, CacheLookup) {
+        let start = Instant::now();
+        let Some(key) = self.key(principal, action, resource, context) else {
+            eprintln!("decision cache miss: context is not valid JSON");
+            return (None, CacheLookup { hit: false, cache_lookup_us: start.elapsed().as_micros() });
+        };
+
+        let decision = {
+            let mut conn = self.conn.lock().expect("Redis connection lock poisoned");
+            let cached: Option<String> = match redis::cmd("GET").arg(&key).query(&mut *conn) {
+                Ok(cached) => cached,
+                Err(err) => {
+                    eprintln!("decision cache miss: Redis GET failed: {err}");
+                    None
+                }
+            };
+            cached.and_then(|raw| match serde_json::from_str::<StoredDecision>(&raw) {
+                Ok(stored) => Some(CachedDecision {
+                    decision: stored.decision,
+                    determining_policies: stored.determining_policies,
+                    errors: stored.errors,
+                }),
+                Err(err) => {
+                    eprintln!("decision cache miss: corrupt cache entry: {err}");
+                    None
+                }
+            })
+        };
+        let cache_lookup_us = start.elapsed().as_micros();
+
+        let hit = decision.is_some();
+        (decision, CacheLookup { hit, cache_lookup_us })
+    }
+
+    /// Stores a decision for this request, expiring after `ttl_seconds`.
+    /// Logs and gives up on any failure instead of panicking — a failed
+    /// store just means the next identical request re-runs the
+    /// `Authorizer`, not that this one should fail.
+    pub fn set(&self, principal: &str, action: &str, resource: &str, context: &str, decision: &CachedDecision) {
+        let Some(key) = self.key(principal, action, resource, context) else {
+            eprintln!("decision not cached: context is not valid JSON");
+            return;
+        };
+        let stored = StoredDecision {
+            decision: decision.decision.clone(),
+            determining_policies: decision.determining_policies.clone(),
+            errors: decision.errors.clone(),
+        };
+        let raw = match serde_json::to_string(&stored) {
+            Ok(raw) => raw,
+            Err(err) => {
+                eprintln!("decision not cached: failed to serialize cache entry: {err}");
+                return;
+            }
+        };
+
+        let mut conn = self.conn.lock().expect("Redis connection lock poisoned");
+        if let Err(err) = redis::cmd("SET").arg(&key).arg(&raw).arg("EX").arg(self.ttl_seconds).query::<()>(&mut *conn) {
+            eprintln!("decision not cached: Redis SET failed: {err}");
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredDecision {
+    decision: String,
+    determining_policies: Vec<String>,
+    errors: Vec<String>,
+}
+
+/// Re-serializes parsed JSON with object keys sorted explicitly, so that
+/// key-order-insensitive-but-otherwise-identical context objects hash to
+/// the same cache key. Sorted by hand (rather than relying on
+/// `serde_json::Value`'s default `BTreeMap` ordering) so this stays
+/// correct even if the `preserve_order` feature is ever enabled elsewhere
+/// in the dependency tree.
+fn canonicalize_json(context: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(context).ok()?;
+    serde_json::to_string(&sort_keys(value)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differently_ordered_keys_canonicalize_the_same() {
+        let a = canonicalize_json(r#"{"mfa_authenticated": true, "request_client_ip": "1.2.3.4"}"#);
+        let b = canonicalize_json(r#"{"request_client_ip": "1.2.3.4", "mfa_authenticated": true}"#);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn nested_object_keys_are_sorted_too() {
+        let a = canonicalize_json(r#"{"outer": {"b": 1, "a": 2}}"#);
+        let b = canonicalize_json(r#"{"outer": {"a": 2, "b": 1}}"#);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn invalid_context_json_canonicalizes_to_none() {
+        assert_eq!(canonicalize_json("not json"), None);
+    }
+}
+
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let sorted: std::collections::BTreeMap<String, serde_json::Value> =
+                map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+            serde_json::Value::Object(sorted.into_iter().collect())
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
@@ -1,13 +1,50 @@
-use cedar_policy::{Authorizer, Context, Entities, EntityUid, PolicySet, Request, Response};
-use clap::Parser;
+use cedar_policy::EntityUid;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::time::Instant;
-use serde::Serialize;
+
+mod authorize;
+mod batch;
+mod cache;
+mod schema;
+mod server;
+mod token;
+use authorize::{authorize_with_timing, TimingOutput};
+use cache::{CachedDecision, DecisionCache};
+use token::{AuthServerMetadata, ClaimMapping, ClientCredentials, TokenEndpointAuthMethod};
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Parse the PolicySet/Entities once and answer authorization requests
+    /// over stdio and/or HTTP, reusing the cached Authorizer and policies
+    Serve {
+        /// Path to the Cedar policy file
+        #[arg(short = 'P', long)]
+        policy_file: PathBuf,
+
+        /// Path to the entities JSON file
+        #[arg(short, long)]
+        entities_file: PathBuf,
+
+        /// Address to serve HTTP requests on (e.g. "127.0.0.1:8080"); stdio
+        /// is always served in addition
+        #[arg(long)]
+        http_addr: Option<String>,
+
+        /// Reload the policy set and entities on SIGHUP or when the policy
+        /// file changes on disk, instead of requiring a restart
+        #[arg(long, default_value = "false")]
+        hot_reload: bool,
+    },
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "cedar-authorize")]
 #[command(about = "Authorize requests using Cedar Policy", long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Principal entity (e.g., "User::\"alice\"")
     #[arg(short, long)]
     principal: Option<String>,
@@ -35,84 +72,88 @@ struct Args {
     /// Output timing information as JSON
     #[arg(long, default_value = "false")]
     timing: bool,
-}
 
-#[derive(Serialize)]
-struct TimingOutput {
-    decision: String,
-    parse_policy_us: u128,
-    parse_context_us: u128,
-    parse_entities_us: u128,
-    build_request_us: u128,
-    authorization_us: u128,
-    total_us: u128,
+    /// Bearer token (JWT or opaque) to resolve into the Cedar context instead
+    /// of trusting --context from the caller
+    #[arg(long, requires = "issuer")]
+    token: Option<String>,
+
+    /// Authorization server issuer, used to verify `iss` and to look up
+    /// other endpoints
+    #[arg(long)]
+    issuer: Option<String>,
+
+    /// RFC 7662 introspection endpoint, used when --jwks-url isn't given
+    /// (i.e. the token is opaque)
+    #[arg(long, requires_all = ["client_id", "client_secret"])]
+    introspection_endpoint: Option<String>,
+
+    /// JWKS URL used to verify the token locally as a JWT
+    #[arg(long)]
+    jwks_url: Option<String>,
+
+    /// Expected `aud` claim
+    #[arg(long)]
+    audience: Option<String>,
+
+    /// The only JWS algorithm accepted for a --jwks-url verified JWT; never
+    /// taken from the token itself, to avoid alg-confusion attacks
+    #[arg(long, default_value = "RS256")]
+    jwt_algorithm: String,
+
+    /// How the client authenticates to --introspection-endpoint
+    #[arg(long, default_value = "client_secret_basic")]
+    token_endpoint_auth_method: String,
+
+    /// Client id for introspection
+    #[arg(long, requires = "introspection_endpoint")]
+    client_id: Option<String>,
+
+    /// Client secret for introspection
+    #[arg(long, requires = "introspection_endpoint")]
+    client_secret: Option<String>,
+
+    /// Extra `claim=context_key` mappings, beyond the standard scope/sub/amr/acr
+    /// ones, projected from the token into the context. May be repeated.
+    #[arg(long = "map-claim")]
+    claim_map: Vec<String>,
+
+    /// Redis URL for the decision cache (e.g. "redis://127.0.0.1/"); when
+    /// unset, every request runs the full Authorizer path
+    #[arg(long)]
+    cache_url: Option<String>,
+
+    /// How long a cached decision stays valid
+    #[arg(long, default_value = "30")]
+    cache_ttl: u64,
+
+    /// Cedar schema file (JSON or Cedar schema syntax) to validate the
+    /// policy set, entities, and context against before authorizing
+    #[arg(long)]
+    schema: Option<PathBuf>,
+
+    /// Evaluate every request in this file against the policy set/entities,
+    /// parsed once, instead of a single --principal/--action/--resource
+    #[arg(long)]
+    batch: Option<String>,
 }
 
-/// Authorize a request using Cedar Policy with timing
-pub fn authorize_with_timing(
-    principal: &str,
-    action: &str,
-    resource: &str,
-    policies: &str,
-    entities: &str,
-    context: Option<&str>,
-) -> (Response, TimingOutput) {
-    let total_start = Instant::now();
-
-    // Parse principal, action, resource
-    let principal = EntityUid::from_str(principal).expect("failed to parse principal");
-    let action = EntityUid::from_str(action).expect("failed to parse action");
-    let resource = EntityUid::from_str(resource).expect("failed to parse resource");
-
-    // Parse context
-    let context_start = Instant::now();
-    let context_str = context.unwrap_or("{}");
-    let context_json: serde_json::Value =
-        serde_json::from_str(context_str).expect("failed to parse context JSON");
-    let context = Context::from_json_value(context_json, None).expect("failed to create context");
-    let parse_context_us = context_start.elapsed().as_micros();
-
-    // Build request
-    let request_start = Instant::now();
-    let request =
-        Request::new(principal, action, resource, context, None).expect("failed to create request");
-    let build_request_us = request_start.elapsed().as_micros();
-
-    // Parse policies
-    let policy_start = Instant::now();
-    let policy_set = PolicySet::from_str(policies).expect("failed to parse policies");
-    let parse_policy_us = policy_start.elapsed().as_micros();
-
-    // Parse entities
-    let entities_start = Instant::now();
-    let entities = Entities::from_json_str(entities, None).expect("failed to parse entities");
-    let parse_entities_us = entities_start.elapsed().as_micros();
-
-    // Authorization
-    let auth_start = Instant::now();
-    let authorizer = Authorizer::new();
-    let response = authorizer.is_authorized(&request, &policy_set, &entities);
-    let authorization_us = auth_start.elapsed().as_micros();
-
-    let total_us = total_start.elapsed().as_micros();
-
-    let timing = TimingOutput {
-        decision: format!("{:?}", response.decision()),
-        parse_policy_us,
-        parse_context_us,
-        parse_entities_us,
-        build_request_us,
-        authorization_us,
-        total_us,
-    };
-
-    (response, timing)
+/// Prints `error` as a JSON object to stdout and exits non-zero, matching
+/// the structured-diagnostics style the --schema and --batch paths use
+/// instead of panicking.
+fn fail<E: std::fmt::Display>(error: E) -> ! {
+    println!("{}", serde_json::json!({ "error": error.to_string() }));
+    std::process::exit(1);
 }
 
 fn main() {
     let args = Args::parse();
 
-    let principal = args.principal.as_deref().unwrap_or("User::\"alice\"");
+    if let Some(Command::Serve { policy_file, entities_file, http_addr, hot_reload }) = args.command {
+        server::serve(policy_file, entities_file, http_addr, hot_reload);
+        return;
+    }
+
     let action = args.action.as_deref().unwrap_or("Action::\"update\"");
     let resource = args.resource.as_deref().unwrap_or("Photo::\"flower.jpg\"");
     let policies = args.policy.as_deref().unwrap_or(
@@ -126,14 +167,196 @@ fn main() {
         };"#,
     );
     let entities = &args.entities;
+
+    if let Some(batch_path) = &args.batch {
+        let (results, timing) = batch::run_batch(batch_path, policies, entities).unwrap_or_else(fail);
+        for result in &results {
+            println!("{}", serde_json::to_string(result).unwrap());
+        }
+        if args.timing {
+            eprintln!("{}", serde_json::to_string(&timing).unwrap());
+        }
+        return;
+    }
+
+    // A --token takes over context construction: the context comes from
+    // verified claims instead of caller-supplied JSON.
+    let resolved_token = args.token.as_deref().map(|token| -> Result<_, token::TokenError> {
+        let metadata = AuthServerMetadata {
+            issuer: args.issuer.clone().expect("--issuer is required with --token"),
+            introspection_endpoint: args.introspection_endpoint.clone(),
+            token_endpoint_auth_method: TokenEndpointAuthMethod::parse(&args.token_endpoint_auth_method)?,
+            audience: args.audience.clone(),
+            algorithm: token::parse_algorithm(&args.jwt_algorithm)?,
+        };
+        let claim_map: Vec<ClaimMapping> =
+            args.claim_map.iter().map(|s| ClaimMapping::parse(s)).collect::<Result<_, _>>()?;
+
+        match &args.jwks_url {
+            Some(jwks_url) => token::resolve_jwt(token, &metadata, jwks_url, &claim_map),
+            None => {
+                let client = ClientCredentials {
+                    // --client-id/--client-secret and --introspection-endpoint
+                    // require each other, so a missing credential here means
+                    // no introspection endpoint was given.
+                    client_id: args.client_id.clone().ok_or(token::TokenError::MissingIntrospectionEndpoint)?,
+                    client_secret: args.client_secret.clone().ok_or(token::TokenError::MissingIntrospectionEndpoint)?,
+                };
+                token::resolve_opaque(token, &metadata, &client, &claim_map)
+            }
+        }
+    }).transpose().unwrap_or_else(fail);
+
+    if let Some(resolved) = &resolved_token {
+        if !resolved.active {
+            let outcome = authorize::AuthzOutcome {
+                decision: "Deny".to_string(),
+                diagnostics: authorize::AuthzDiagnostics { determining_policies: Vec::new(), errors: Vec::new() },
+                timing: TimingOutput {
+                    parse_policy_us: 0,
+                    parse_context_us: 0,
+                    parse_entities_us: 0,
+                    build_request_us: 0,
+                    authorization_us: 0,
+                    total_us: 0,
+                    cache_hit: None,
+                    cache_lookup_us: None,
+                },
+            };
+            print_outcome(&outcome, args.timing);
+            return;
+        }
+    }
+
+    let principal = args
+        .principal
+        .clone()
+        .or_else(|| resolved_token.as_ref().and_then(|r| r.principal.clone()))
+        .unwrap_or_else(|| "User::\"alice\"".to_string());
+
     let context_default = r#"{"mfa_authenticated": true, "request_client_ip": "222.222.222.222", "oidc_scope": "profile"}"#;
-    let context = args.context.as_deref().or(Some(context_default));
+    let context_from_token = resolved_token.as_ref().map(|r| r.context.to_string());
+    let context = context_from_token
+        .as_deref()
+        .or(args.context.as_deref())
+        .or(Some(context_default));
+
+    if let Some(schema_path) = &args.schema {
+        let mut diagnostics = Vec::new();
+
+        let cedar_schema = match schema::load_schema(schema_path) {
+            Ok(cedar_schema) => Some(cedar_schema),
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                None
+            }
+        };
+
+        let policy_set = cedar_schema.as_ref().and_then(|_| match schema::parse_policies(policies) {
+            Ok(policy_set) => Some(policy_set),
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                None
+            }
+        });
+
+        if let (Some(cedar_schema), Some(policy_set)) = (&cedar_schema, &policy_set) {
+            diagnostics.extend(schema::validate_policies(policy_set, cedar_schema));
 
-    let (response, timing) = authorize_with_timing(principal, action, resource, policies, entities, context);
+            if let Err(diagnostic) = schema::parse_entities_with_schema(entities, cedar_schema) {
+                diagnostics.push(diagnostic);
+            }
+
+            let action_uid = EntityUid::from_str(action).map_err(|err| schema::ValidationDiagnostic {
+                severity: schema::Severity::Error,
+                source: action.to_string(),
+                message: err.to_string(),
+            });
+            let context_json: Result<serde_json::Value, schema::ValidationDiagnostic> =
+                serde_json::from_str(context.unwrap_or("{}")).map_err(|err| schema::ValidationDiagnostic {
+                    severity: schema::Severity::Error,
+                    source: "context".to_string(),
+                    message: err.to_string(),
+                });
+
+            match (action_uid, context_json) {
+                (Ok(action_uid), Ok(context_json)) => {
+                    if let Err(diagnostic) = schema::validate_context(context_json, &action_uid, cedar_schema) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
+                (action_uid, context_json) => {
+                    diagnostics.extend(action_uid.err());
+                    diagnostics.extend(context_json.err());
+                }
+            }
+        }
+
+        if !diagnostics.is_empty() {
+            println!("{}", serde_json::to_string(&diagnostics).unwrap());
+            std::process::exit(1);
+        }
+    }
+
+    let context_for_cache = context.unwrap_or("{}").to_string();
+    let cache = args.cache_url.as_deref().and_then(|url| DecisionCache::connect(url, args.cache_ttl, policies, entities));
+
+    if let Some(cache) = &cache {
+        let (cached, lookup) = cache.get(&principal, action, resource, &context_for_cache);
+        if let Some(cached) = cached {
+            if args.timing {
+                let timing = TimingOutput {
+                    parse_policy_us: 0,
+                    parse_context_us: 0,
+                    parse_entities_us: 0,
+                    build_request_us: 0,
+                    authorization_us: 0,
+                    total_us: lookup.cache_lookup_us,
+                    cache_hit: Some(true),
+                    cache_lookup_us: Some(lookup.cache_lookup_us),
+                };
+                println!("{}", serde_json::to_string(&serde_json::json!({
+                    "decision": cached.decision,
+                    "diagnostics": { "determining_policies": cached.determining_policies, "errors": cached.errors },
+                    "timing": timing,
+                })).unwrap());
+            } else {
+                println!("{}", cached.decision);
+            }
+            return;
+        }
+
+        let mut outcome = authorize_with_timing(&principal, action, resource, policies, entities, context).unwrap_or_else(fail);
+        cache.set(
+            &principal,
+            action,
+            resource,
+            &context_for_cache,
+            &CachedDecision {
+                decision: outcome.decision.clone(),
+                determining_policies: outcome.diagnostics.determining_policies.clone(),
+                errors: outcome.diagnostics.errors.clone(),
+            },
+        );
+        outcome.timing.cache_hit = Some(false);
+        outcome.timing.cache_lookup_us = Some(lookup.cache_lookup_us);
+
+        print_outcome(&outcome, args.timing);
+        return;
+    }
+
+    let outcome = authorize_with_timing(&principal, action, resource, policies, entities, context).unwrap_or_else(fail);
+    print_outcome(&outcome, args.timing);
+}
 
-    if args.timing {
-        println!("{}", serde_json::to_string(&timing).unwrap());
+fn print_outcome(outcome: &authorize::AuthzOutcome, timing: bool) {
+    if timing {
+        println!("{}", serde_json::to_string(&serde_json::json!({
+            "decision": outcome.decision,
+            "diagnostics": &outcome.diagnostics,
+            "timing": &outcome.timing,
+        })).unwrap());
     } else {
-        println!("{:?}", response.decision());
+        println!("{}", outcome.decision);
     }
 }
\ No newline at end of file
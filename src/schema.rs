@@ -0,0 +1,191 @@
+//! Cedar schema validation: catches the class of bugs that otherwise show
+//! up as a confusing `Deny` (a typo'd context attribute that no policy
+//! matches) or a panic (a malformed entity), by checking the `PolicySet`,
+//! `Entities`, and `Context` against a declared Cedar schema before
+//! authorization ever runs.
+
+use cedar_policy::{
+    Context, Entities, PolicySet, Schema, ValidationMode, Validator,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// One validation problem, shaped for machine consumption (an MCP
+/// deployment failing fast in CI needs structured output, not a panic).
+#[derive(Serialize)]
+pub struct ValidationDiagnostic {
+    pub severity: Severity,
+    pub source: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Loads and parses a Cedar schema file (JSON or Cedar schema syntax).
+/// Returns a diagnostic instead of panicking on a missing or malformed
+/// file, so a bad --schema fails a CI run with the same JSON error list
+/// as a bad policy or entity, not a stack trace.
+pub fn load_schema(schema_path: &Path) -> Result<Schema, ValidationDiagnostic> {
+    let to_diagnostic = |message: String| ValidationDiagnostic {
+        severity: Severity::Error,
+        source: schema_path.display().to_string(),
+        message,
+    };
+
+    let raw = fs::read_to_string(schema_path).map_err(|err| to_diagnostic(err.to_string()))?;
+
+    if schema_path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+        Schema::from_json_str(&raw).map_err(|err| to_diagnostic(err.to_string()))
+    } else {
+        Schema::from_cedarschema_str(&raw)
+            .map(|(schema, _warnings)| schema)
+            .map_err(|err| to_diagnostic(err.to_string()))
+    }
+}
+
+/// Parses `policies` into a `PolicySet`, as a diagnostic-producing
+/// counterpart to `PolicySet::from_str` for the --schema path: a
+/// syntactically broken policy is just another validation error here,
+/// not a panic.
+pub fn parse_policies(policies: &str) -> Result<PolicySet, ValidationDiagnostic> {
+    PolicySet::from_str(policies).map_err(|err| ValidationDiagnostic {
+        severity: Severity::Error,
+        source: "policies".to_string(),
+        message: err.to_string(),
+    })
+}
+
+/// Validates `policy_set` against `schema`, returning one diagnostic per
+/// type error and one per validator warning (severity `warning`), each
+/// carrying the offending policy id.
+pub fn validate_policies(policy_set: &PolicySet, schema: &Schema) -> Vec<ValidationDiagnostic> {
+    let validator = Validator::new(schema.clone());
+    let result = validator.validate(policy_set, ValidationMode::default());
+
+    result
+        .validation_errors()
+        .map(|error| ValidationDiagnostic {
+            severity: Severity::Error,
+            source: error.policy_id().to_string(),
+            message: error.to_string(),
+        })
+        .chain(result.validation_warnings().map(|warning| ValidationDiagnostic {
+            severity: Severity::Warning,
+            source: warning.policy_id().to_string(),
+            message: warning.to_string(),
+        }))
+        .collect()
+}
+
+/// Parses `entities_str` against `schema` so entity shapes (attribute
+/// names and types, declared parents) are checked, instead of just
+/// accepting whatever JSON is passed in.
+pub fn parse_entities_with_schema(entities_str: &str, schema: &Schema) -> Result<Entities, ValidationDiagnostic> {
+    Entities::from_json_str(entities_str, Some(schema)).map_err(|err| ValidationDiagnostic {
+        severity: Severity::Error,
+        source: "entities".to_string(),
+        message: err.to_string(),
+    })
+}
+
+/// Validates `context_json` against the context type declared for
+/// `action` in `schema`, rejecting unknown or wrong-typed attributes
+/// before they can silently fail to match any policy.
+pub fn validate_context(
+    context_json: serde_json::Value,
+    action: &cedar_policy::EntityUid,
+    schema: &Schema,
+) -> Result<Context, ValidationDiagnostic> {
+    Context::from_json_value(context_json, Some((schema, action))).map_err(|err| ValidationDiagnostic {
+        severity: Severity::Error,
+        source: action.to_string(),
+        message: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cedar_policy::EntityUid;
+
+    fn test_schema() -> Schema {
+        Schema::from_cedarschema_str(
+            r#"
+            entity User;
+            entity Photo;
+            action "view" appliesTo {
+                principal: [User],
+                resource: [Photo],
+                context: {
+                    mfa_authenticated: Bool
+                }
+            };
+            "#,
+        )
+        .expect("test schema should parse")
+        .0
+    }
+
+    #[test]
+    fn validate_policies_passes_a_well_typed_policy() {
+        let schema = test_schema();
+        let policy_set = parse_policies(
+            r#"permit(principal, action == Action::"view", resource) when { context.mfa_authenticated == true };"#,
+        )
+        .unwrap();
+
+        assert!(validate_policies(&policy_set, &schema).is_empty());
+    }
+
+    #[test]
+    fn validate_policies_reports_an_unknown_context_attribute() {
+        let schema = test_schema();
+        let policy_set = parse_policies(
+            r#"permit(principal, action == Action::"view", resource) when { context.nonexistent == true };"#,
+        )
+        .unwrap();
+
+        assert!(!validate_policies(&policy_set, &schema).is_empty());
+    }
+
+    #[test]
+    fn parse_entities_with_schema_accepts_declared_entity_types() {
+        let schema = test_schema();
+        let entities = r#"[{"uid": {"type": "User", "id": "alice"}, "attrs": {}, "parents": []}]"#;
+
+        assert!(parse_entities_with_schema(entities, &schema).is_ok());
+    }
+
+    #[test]
+    fn parse_entities_with_schema_rejects_an_undeclared_entity_type() {
+        let schema = test_schema();
+        let entities = r#"[{"uid": {"type": "Unknown", "id": "alice"}, "attrs": {}, "parents": []}]"#;
+
+        assert!(parse_entities_with_schema(entities, &schema).is_err());
+    }
+
+    #[test]
+    fn validate_context_accepts_a_well_typed_context() {
+        let schema = test_schema();
+        let action = EntityUid::from_str("Action::\"view\"").unwrap();
+        let context = serde_json::json!({"mfa_authenticated": true});
+
+        assert!(validate_context(context, &action, &schema).is_ok());
+    }
+
+    #[test]
+    fn validate_context_rejects_a_wrong_typed_attribute() {
+        let schema = test_schema();
+        let action = EntityUid::from_str("Action::\"view\"").unwrap();
+        let context = serde_json::json!({"mfa_authenticated": "not-a-bool"});
+
+        assert!(validate_context(context, &action, &schema).is_err());
+    }
+}
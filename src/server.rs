@@ -0,0 +1,343 @@
+//! Long-running authorization server: parses the `PolicySet` and `Entities`
+//! once, then answers many authorization requests against the cached copies
+//! instead of re-parsing on every call.
+//!
+//! Requests arrive over stdio (newline-delimited JSON, fed straight into an
+//! MCP stdio transport) and, optionally, over a small HTTP endpoint. Both
+//! transports share the same compiled `PolicySet`/`Entities` state behind an
+//! `RwLock`, which a SIGHUP or a policy-file change can swap out without
+//! restarting the process.
+
+use cedar_policy::{Authorizer, Context, Entities, EntityUid, PolicySet, Request};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+/// Timing for the one-time policy/entity parse at startup (or reload).
+#[derive(Serialize, Clone, Copy)]
+pub struct StartupTiming {
+    pub parse_policy_us: u128,
+    pub parse_entities_us: u128,
+}
+
+/// Timing for a single request against the already-compiled state.
+#[derive(Serialize)]
+pub struct PerRequestTiming {
+    pub parse_context_us: u128,
+    pub build_request_us: u128,
+    pub authorization_us: u128,
+}
+
+/// One line of stdio/HTTP input: everything that varies between requests.
+#[derive(Deserialize)]
+struct AuthzRequest {
+    principal: String,
+    action: String,
+    resource: String,
+    context: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct AuthzResponse {
+    decision: String,
+    per_request: PerRequestTiming,
+}
+
+/// The compiled state shared by every request, swappable on reload.
+struct Compiled {
+    policy_set: PolicySet,
+    entities: Entities,
+    startup: StartupTiming,
+}
+
+/// Parses the policy/entities files into a fresh `Compiled` state. Returns
+/// `Err` instead of panicking, so a hot-reload triggered by a half-edited
+/// file can log the problem and keep serving the last-good state rather
+/// than taking down the reload thread (and, with it, hot-reload) for good.
+fn compile(policy_path: &PathBuf, entities_path: &PathBuf) -> Result<Compiled, String> {
+    let policy_start = Instant::now();
+    let policy_str = fs::read_to_string(policy_path).map_err(|err| err.to_string())?;
+    let policy_set = PolicySet::from_str(&policy_str).map_err(|err| err.to_string())?;
+    let parse_policy_us = policy_start.elapsed().as_micros();
+
+    let entities_start = Instant::now();
+    let entities_str = fs::read_to_string(entities_path).map_err(|err| err.to_string())?;
+    let entities = Entities::from_json_str(&entities_str, None).map_err(|err| err.to_string())?;
+    let parse_entities_us = entities_start.elapsed().as_micros();
+
+    Ok(Compiled {
+        policy_set,
+        entities,
+        startup: StartupTiming { parse_policy_us, parse_entities_us },
+    })
+}
+
+/// Answers one request against the already-compiled state. Returns `Err`
+/// for a bad principal/action/resource/context instead of panicking — a
+/// single malformed request must not take down the whole long-running
+/// server (or, on the HTTP side, the thread serving every other client).
+fn answer(authorizer: &Authorizer, compiled: &Compiled, req: &AuthzRequest) -> Result<AuthzResponse, String> {
+    let context_start = Instant::now();
+    let context_json = req.context.clone().unwrap_or(serde_json::Value::Object(Default::default()));
+    let context = Context::from_json_value(context_json, None).map_err(|err| err.to_string())?;
+    let parse_context_us = context_start.elapsed().as_micros();
+
+    let build_start = Instant::now();
+    let principal = EntityUid::from_str(&req.principal).map_err(|err| err.to_string())?;
+    let action = EntityUid::from_str(&req.action).map_err(|err| err.to_string())?;
+    let resource = EntityUid::from_str(&req.resource).map_err(|err| err.to_string())?;
+    let request = Request::new(principal, action, resource, context, None).map_err(|err| err.to_string())?;
+    let build_request_us = build_start.elapsed().as_micros();
+
+    let auth_start = Instant::now();
+    let response = authorizer.is_authorized(&request, &compiled.policy_set, &compiled.entities);
+    let authorization_us = auth_start.elapsed().as_micros();
+
+    Ok(AuthzResponse {
+        decision: format!("{:?}", response.decision()),
+        per_request: PerRequestTiming { parse_context_us, build_request_us, authorization_us },
+    })
+}
+
+/// Writes one line to stdout and flushes it, logging and reporting failure
+/// instead of panicking. A client closing its end of the stdio pipe (normal
+/// when an MCP stdio transport shuts down) must not take down the whole
+/// long-running server; returns `false` once the pipe is gone so the caller
+/// can stop trying to serve stdio instead of failing the same write forever.
+fn write_line(out: &mut impl Write, line: &str) -> bool {
+    if let Err(err) = writeln!(out, "{line}").and_then(|_| out.flush()) {
+        eprintln!("stdio write failed, stopping stdio loop: {err}");
+        return false;
+    }
+    true
+}
+
+/// Runs the stdio loop: one `AuthzRequest` JSON object per line in, one
+/// `AuthzResponse` JSON object per line out.
+fn serve_stdio(authorizer: &Authorizer, state: &Arc<RwLock<Compiled>>) {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                if !write_line(&mut out, &serde_json::json!({ "error": err.to_string() }).to_string()) {
+                    return;
+                }
+                continue;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let req: AuthzRequest = match serde_json::from_str(&line) {
+            Ok(req) => req,
+            Err(err) => {
+                if !write_line(&mut out, &serde_json::json!({ "error": err.to_string() }).to_string()) {
+                    return;
+                }
+                continue;
+            }
+        };
+        let compiled = state.read().expect("compiled state lock poisoned");
+        let body = match answer(authorizer, &compiled, &req) {
+            Ok(response) => serde_json::to_string(&response).unwrap(),
+            Err(err) => serde_json::json!({ "error": err }).to_string(),
+        };
+        if !write_line(&mut out, &body) {
+            return;
+        }
+    }
+}
+
+/// Runs a minimal HTTP endpoint (`POST /authorize` with an `AuthzRequest`
+/// body) on `addr`, blocking the calling thread.
+fn serve_http(authorizer: &Authorizer, state: &Arc<RwLock<Compiled>>, addr: &str) {
+    let server = tiny_http::Server::http(addr).unwrap_or_else(|err| panic!("failed to bind {addr}: {err}"));
+    for mut request in server.incoming_requests() {
+        if request.method() != &tiny_http::Method::Post || request.url() != "/authorize" {
+            let response = tiny_http::Response::from_string(serde_json::json!({ "error": "not found" }).to_string())
+                .with_status_code(404);
+            if let Err(err) = request.respond(response) {
+                eprintln!("failed to write HTTP response: {err}");
+            }
+            continue;
+        }
+        let mut body = String::new();
+        let result = request
+            .as_reader()
+            .read_to_string(&mut body)
+            .map_err(|err| err.to_string())
+            .and_then(|_| serde_json::from_str::<AuthzRequest>(&body).map_err(|err| err.to_string()))
+            .and_then(|req| {
+                let compiled = state.read().expect("compiled state lock poisoned");
+                answer(authorizer, &compiled, &req)
+            });
+
+        let (status, body) = match result {
+            Ok(response) => (200, serde_json::to_string(&response).unwrap()),
+            Err(err) => (400, serde_json::json!({ "error": err }).to_string()),
+        };
+        let response = tiny_http::Response::from_string(body).with_status_code(status);
+        // A client that disconnects before the response is written must not
+        // take down this thread — that would silently kill the HTTP
+        // endpoint for the rest of the process's life.
+        if let Err(err) = request.respond(response) {
+            eprintln!("failed to write HTTP response: {err}");
+        }
+    }
+}
+
+/// Watches for SIGHUP and, on Unix, registers the signal; recompiles and
+/// swaps in fresh state when it fires. Runs on its own thread for the
+/// lifetime of the process.
+#[cfg(unix)]
+fn spawn_reload_on_sighup(policy_path: PathBuf, entities_path: PathBuf, state: Arc<RwLock<Compiled>>) {
+    use signal_hook::consts::SIGHUP;
+    use signal_hook::iterator::Signals;
+
+    std::thread::spawn(move || {
+        let mut signals = Signals::new([SIGHUP]).expect("failed to register SIGHUP handler");
+        for _ in signals.forever() {
+            match compile(&policy_path, &entities_path) {
+                Ok(compiled) => {
+                    *state.write().expect("compiled state lock poisoned") = compiled;
+                    eprintln!("reloaded policy set and entities on SIGHUP");
+                }
+                Err(err) => eprintln!("SIGHUP reload failed, keeping previous policy set/entities: {err}"),
+            }
+        }
+    });
+}
+
+/// Watches `policy_path` for modifications and reloads on change. Runs on
+/// its own thread for the lifetime of the process.
+fn spawn_reload_on_file_change(policy_path: PathBuf, entities_path: PathBuf, state: Arc<RwLock<Compiled>>) {
+    use notify::{RecursiveMode, Watcher};
+
+    std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).expect("failed to create file watcher");
+        watcher
+            .watch(&policy_path, RecursiveMode::NonRecursive)
+            .expect("failed to watch policy file");
+
+        for event in rx {
+            if event.is_ok() {
+                match compile(&policy_path, &entities_path) {
+                    Ok(compiled) => {
+                        *state.write().expect("compiled state lock poisoned") = compiled;
+                        eprintln!("reloaded policy set and entities after file change");
+                    }
+                    Err(err) => eprintln!("file-change reload failed, keeping previous policy set/entities: {err}"),
+                }
+            }
+        }
+    });
+}
+
+/// Parses/validates the `PolicySet` and `Entities` once, reports the
+/// startup timing, then serves authorization requests over stdio and,
+/// if `http_addr` is given, HTTP — until the process is killed.
+pub fn serve(policy_path: PathBuf, entities_path: PathBuf, http_addr: Option<String>, hot_reload: bool) {
+    let compiled = compile(&policy_path, &entities_path).unwrap_or_else(|err| {
+        println!("{}", serde_json::json!({ "error": err }));
+        std::process::exit(1);
+    });
+    eprintln!("{}", serde_json::to_string(&compiled.startup).unwrap());
+
+    let state = Arc::new(RwLock::new(compiled));
+
+    if hot_reload {
+        #[cfg(unix)]
+        spawn_reload_on_sighup(policy_path.clone(), entities_path.clone(), Arc::clone(&state));
+        spawn_reload_on_file_change(policy_path, entities_path, Arc::clone(&state));
+    }
+
+    let authorizer = Authorizer::new();
+
+    if let Some(addr) = http_addr {
+        let http_state = Arc::clone(&state);
+        let http_authorizer = Authorizer::new();
+        std::thread::spawn(move || serve_http(&http_authorizer, &http_state, &addr));
+    }
+
+    serve_stdio(&authorizer, &state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a throwaway file under the OS temp dir, unique
+    /// per call so parallel tests don't collide.
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "cedar-authorize-server-test-{name}-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos(),
+        ));
+        fs::write(&path, contents).expect("failed to write temp file");
+        path
+    }
+
+    fn try_compile(policy: &str, entities: &str) -> Result<Compiled, String> {
+        let policy_path = temp_file("policy", policy);
+        let entities_path = temp_file("entities", entities);
+        let result = compile(&policy_path, &entities_path);
+        let _ = fs::remove_file(&policy_path);
+        let _ = fs::remove_file(&entities_path);
+        result
+    }
+
+    fn test_compiled() -> Compiled {
+        try_compile(r#"permit(principal, action, resource);"#, "[]").expect("valid test fixtures should compile")
+    }
+
+    #[test]
+    fn compile_parses_a_valid_policy_and_entities_file() {
+        assert!(try_compile(r#"permit(principal, action, resource);"#, "[]").is_ok());
+    }
+
+    #[test]
+    fn compile_reports_a_malformed_policy_file_instead_of_panicking() {
+        assert!(try_compile("this is not valid cedar", "[]").is_err());
+    }
+
+    #[test]
+    fn compile_reports_malformed_entities_instead_of_panicking() {
+        assert!(try_compile(r#"permit(principal, action, resource);"#, "not json").is_err());
+    }
+
+    fn request(principal: &str) -> AuthzRequest {
+        AuthzRequest {
+            principal: principal.to_string(),
+            action: "Action::\"view\"".to_string(),
+            resource: "Photo::\"flower.jpg\"".to_string(),
+            context: None,
+        }
+    }
+
+    #[test]
+    fn answer_returns_a_decision_for_a_valid_request() {
+        let compiled = test_compiled();
+        let authorizer = Authorizer::new();
+
+        let response = answer(&authorizer, &compiled, &request("User::\"alice\"")).expect("valid request should not error");
+        assert_eq!(response.decision, "Allow");
+    }
+
+    #[test]
+    fn answer_reports_a_malformed_principal_instead_of_panicking() {
+        let compiled = test_compiled();
+        let authorizer = Authorizer::new();
+
+        assert!(answer(&authorizer, &compiled, &request("not a valid uid")).is_err());
+    }
+}
@@ -0,0 +1,407 @@
+//! Resolves a bearer token presented by an MCP client into a Cedar `Context`.
+//!
+//! Two verification paths are supported, chosen by what the caller provides:
+//! - a JWKS URL: the token is treated as a JWT and verified locally (signature,
+//!   `exp`, `iss`, and `aud` when an audience is configured).
+//! - an introspection endpoint: the token is treated as opaque and verified by
+//!   POSTing it to the authorization server (RFC 7662).
+//!
+//! Either path ends in the same place: a map of resolved claims that gets
+//! projected into the JSON value handed to `Context::from_json_value`.
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Authorization-server metadata needed to verify a token, roughly the subset
+/// of RFC 8414 (`/.well-known/oauth-authorization-server`) we actually use.
+#[derive(Debug, Clone)]
+pub struct AuthServerMetadata {
+    pub issuer: String,
+    pub introspection_endpoint: Option<String>,
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    pub audience: Option<String>,
+    /// The only JWS algorithm this deployment accepts, configured out of
+    /// band — never taken from the token's own (attacker-controlled)
+    /// header, to avoid the classic JWT alg-confusion attack.
+    pub algorithm: Algorithm,
+}
+
+/// How we authenticate to `introspection_endpoint` (RFC 7662 section 2.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenEndpointAuthMethod {
+    ClientSecretBasic,
+    ClientSecretPost,
+}
+
+impl TokenEndpointAuthMethod {
+    pub fn parse(s: &str) -> Result<TokenEndpointAuthMethod, TokenError> {
+        match s {
+            "client_secret_basic" => Ok(TokenEndpointAuthMethod::ClientSecretBasic),
+            "client_secret_post" => Ok(TokenEndpointAuthMethod::ClientSecretPost),
+            other => Err(TokenError::UnsupportedAuthMethod(other.to_string())),
+        }
+    }
+}
+
+/// Parses a `--jwt-algorithm` value into the `jsonwebtoken::Algorithm` the
+/// deployment accepts.
+pub fn parse_algorithm(s: &str) -> Result<Algorithm, TokenError> {
+    match s {
+        "RS256" => Ok(Algorithm::RS256),
+        "RS384" => Ok(Algorithm::RS384),
+        "RS512" => Ok(Algorithm::RS512),
+        "PS256" => Ok(Algorithm::PS256),
+        "PS384" => Ok(Algorithm::PS384),
+        "PS512" => Ok(Algorithm::PS512),
+        other => Err(TokenError::UnsupportedAlgorithm(other.to_string())),
+    }
+}
+
+/// Maps a claim in the token to a key in the generated Cedar context, for
+/// claims beyond the standard ones (`scope`, `sub`, `amr`/`acr`) we already
+/// understand.
+#[derive(Debug, Clone)]
+pub struct ClaimMapping {
+    pub claim: String,
+    pub context_key: String,
+}
+
+impl ClaimMapping {
+    /// Parses a `--map-claim claim=context_key` argument.
+    pub fn parse(s: &str) -> Result<ClaimMapping, TokenError> {
+        let (claim, context_key) = s.split_once('=').ok_or_else(|| TokenError::InvalidClaimMapping(s.to_string()))?;
+        Ok(ClaimMapping {
+            claim: claim.to_string(),
+            context_key: context_key.to_string(),
+        })
+    }
+}
+
+/// Client credentials used when authenticating to the introspection endpoint.
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// The result of resolving a token: the projected Cedar context, and a
+/// principal derived from `sub` when the caller didn't supply `--principal`.
+pub struct ResolvedToken {
+    pub context: Value,
+    pub principal: Option<String>,
+    /// `false` when an opaque token's introspection response carried
+    /// `"active": false`; callers should short-circuit to `Deny`.
+    pub active: bool,
+}
+
+/// Every step in verifying a token that can fail, each carrying enough
+/// detail to produce a structured error instead of a panic — an
+/// unreachable IdP or an invalid token should fail the one request, not
+/// the CLI.
+#[derive(Debug)]
+pub enum TokenError {
+    HeaderDecode(String),
+    JwksFetch(String),
+    JwksParse(String),
+    NoMatchingKey { kid: Option<String> },
+    UnsupportedKeyType { kty: String, kid: Option<String> },
+    InvalidKey(String),
+    Verification(String),
+    IntrospectionRequest(String),
+    IntrospectionParse(String),
+    MissingIntrospectionEndpoint,
+    UnsupportedAuthMethod(String),
+    UnsupportedAlgorithm(String),
+    InvalidClaimMapping(String),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::HeaderDecode(source) => write!(f, "failed to decode JWT header: {source}"),
+            TokenError::JwksFetch(source) => write!(f, "failed to fetch JWKS: {source}"),
+            TokenError::JwksParse(source) => write!(f, "failed to parse JWKS document: {source}"),
+            TokenError::NoMatchingKey { kid } => write!(f, "no JWKS key matching kid {kid:?}"),
+            TokenError::UnsupportedKeyType { kty, kid } => {
+                write!(f, "unsupported JWKS key type {kty:?} for kid {kid:?}; only RSA is supported")
+            }
+            TokenError::InvalidKey(source) => write!(f, "invalid RSA key components: {source}"),
+            TokenError::Verification(source) => write!(f, "JWT verification failed (signature, exp, aud, or iss): {source}"),
+            TokenError::IntrospectionRequest(source) => write!(f, "introspection request failed: {source}"),
+            TokenError::IntrospectionParse(source) => write!(f, "failed to parse introspection response: {source}"),
+            TokenError::MissingIntrospectionEndpoint => {
+                write!(f, "--introspection-endpoint is required for opaque tokens")
+            }
+            TokenError::UnsupportedAuthMethod(value) => write!(f, "unsupported token_endpoint_auth_method: {value}"),
+            TokenError::UnsupportedAlgorithm(value) => write!(f, "unsupported jwt_algorithm: {value}"),
+            TokenError::InvalidClaimMapping(value) => write!(f, "claim mapping must be `claim=context_key`, got: {value}"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Quotes a claim value for interpolation into a Cedar entity UID string
+/// literal (e.g. `User::"{sub}"`), so a `sub` containing a `"` or `\` —
+/// attacker-influenced for an opaque/introspected token — can't escape the
+/// quoted literal and inject an unintended entity UID.
+fn quote_entity_id(id: &str) -> String {
+    id.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    scope: Option<String>,
+    sub: Option<String>,
+    amr: Option<Vec<String>>,
+    acr: Option<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct JwksDocument {
+    keys: Vec<JwksKey>,
+}
+
+#[derive(Deserialize)]
+struct JwksKey {
+    kid: Option<String>,
+    kty: String,
+    // Absent on non-RSA keys; defaulted so such a key still parses and is
+    // rejected by the `kty` check rather than failing the whole document.
+    #[serde(default)]
+    n: String,
+    #[serde(default)]
+    e: String,
+}
+
+/// Verifies `token` as a JWT using the JSON Web Key Set at `jwks_url`, then
+/// projects its claims into a Cedar context. Returns `Err` instead of
+/// panicking on a network failure or an invalid token, so an unreachable
+/// IdP or a bad token fails the one request being authorized, not the CLI.
+pub fn resolve_jwt(token: &str, metadata: &AuthServerMetadata, jwks_url: &str, claim_map: &[ClaimMapping]) -> Result<ResolvedToken, TokenError> {
+    // Decoding the header only tells us which key to fetch (`kid`); the
+    // algorithm actually enforced below comes from `metadata.algorithm`,
+    // configured out of band, never from this untrusted header.
+    let header = jsonwebtoken::decode_header(token).map_err(|err| TokenError::HeaderDecode(err.to_string()))?;
+
+    let jwks: JwksDocument = ureq::get(jwks_url)
+        .call()
+        .map_err(|err| TokenError::JwksFetch(err.to_string()))?
+        .into_json()
+        .map_err(|err| TokenError::JwksParse(err.to_string()))?;
+
+    let key = jwks
+        .keys
+        .iter()
+        .find(|k| k.kid == header.kid)
+        .ok_or(TokenError::NoMatchingKey { kid: header.kid.clone() })?;
+
+    if key.kty != "RSA" {
+        return Err(TokenError::UnsupportedKeyType { kty: key.kty.clone(), kid: key.kid.clone() });
+    }
+
+    let decoding_key = DecodingKey::from_rsa_components(&key.n, &key.e).map_err(|err| TokenError::InvalidKey(err.to_string()))?;
+
+    let mut validation = Validation::new(metadata.algorithm);
+    validation.set_issuer(&[&metadata.issuer]);
+    if let Some(audience) = &metadata.audience {
+        validation.set_audience(&[audience]);
+    } else {
+        validation.validate_aud = false;
+    }
+
+    let claims = jsonwebtoken::decode::<HashMap<String, Value>>(token, &decoding_key, &validation)
+        .map_err(|err| TokenError::Verification(err.to_string()))?
+        .claims;
+
+    let principal = claims
+        .get("sub")
+        .and_then(Value::as_str)
+        .map(|sub| format!("User::\"{}\"", quote_entity_id(sub)));
+
+    Ok(ResolvedToken {
+        context: project_claims(&claims, claim_map),
+        principal,
+        active: true,
+    })
+}
+
+/// Verifies `token` as an opaque token by calling `introspection_endpoint`,
+/// then projects the introspection response into a Cedar context. Returns
+/// `Err` instead of panicking on a network failure or a malformed
+/// response, so an unreachable IdP fails the one request, not the CLI.
+pub fn resolve_opaque(
+    token: &str,
+    metadata: &AuthServerMetadata,
+    client: &ClientCredentials,
+    claim_map: &[ClaimMapping],
+) -> Result<ResolvedToken, TokenError> {
+    let endpoint = metadata.introspection_endpoint.as_deref().ok_or(TokenError::MissingIntrospectionEndpoint)?;
+
+    let request = ureq::post(endpoint);
+    let request = match metadata.token_endpoint_auth_method {
+        TokenEndpointAuthMethod::ClientSecretBasic => {
+            request.set("Authorization", &basic_auth_header(&client.client_id, &client.client_secret))
+        }
+        TokenEndpointAuthMethod::ClientSecretPost => request,
+    };
+
+    let form: &[(&str, &str)] = match metadata.token_endpoint_auth_method {
+        TokenEndpointAuthMethod::ClientSecretBasic => &[("token", token)],
+        TokenEndpointAuthMethod::ClientSecretPost => &[],
+    };
+    let response = if metadata.token_endpoint_auth_method == TokenEndpointAuthMethod::ClientSecretPost {
+        request.send_form(&[
+            ("token", token),
+            ("client_id", &client.client_id),
+            ("client_secret", &client.client_secret),
+        ])
+    } else {
+        request.send_form(form)
+    }
+    .map_err(|err| TokenError::IntrospectionRequest(err.to_string()))?;
+
+    let introspection: IntrospectionResponse =
+        response.into_json().map_err(|err| TokenError::IntrospectionParse(err.to_string()))?;
+
+    if !introspection.active {
+        return Ok(ResolvedToken {
+            context: Value::Object(Map::new()),
+            principal: None,
+            active: false,
+        });
+    }
+
+    let mut claims: HashMap<String, Value> = introspection.extra.clone();
+    if let Some(scope) = &introspection.scope {
+        claims.insert("scope".to_string(), Value::String(scope.clone()));
+    }
+    if let Some(sub) = &introspection.sub {
+        claims.insert("sub".to_string(), Value::String(sub.clone()));
+    }
+    if let Some(amr) = &introspection.amr {
+        claims.insert("amr".to_string(), Value::Array(amr.iter().cloned().map(Value::String).collect()));
+    }
+    if let Some(acr) = &introspection.acr {
+        claims.insert("acr".to_string(), Value::String(acr.clone()));
+    }
+
+    let principal = introspection.sub.as_deref().map(|sub| format!("User::\"{}\"", quote_entity_id(sub)));
+
+    Ok(ResolvedToken {
+        context: project_claims(&claims, claim_map),
+        principal,
+        active: true,
+    })
+}
+
+/// Maps standard OIDC/OAuth2 claims plus any configured `claim_map` entries
+/// into the shape `authorize_with_timing`'s context already expects:
+/// `oidc_scope` (a Cedar set), `mfa_authenticated` (bool), and pass-through
+/// custom keys.
+fn project_claims(claims: &HashMap<String, Value>, claim_map: &[ClaimMapping]) -> Value {
+    let mut context = Map::new();
+
+    if let Some(scope) = claims.get("scope").and_then(Value::as_str) {
+        let scopes: Vec<Value> = scope.split(' ').filter(|s| !s.is_empty()).map(|s| Value::String(s.to_string())).collect();
+        context.insert("oidc_scope".to_string(), Value::Array(scopes));
+    }
+
+    let mfa_authenticated = claims
+        .get("amr")
+        .and_then(Value::as_array)
+        .map(|amr| amr.iter().any(|v| v.as_str() == Some("mfa")))
+        .unwrap_or(false)
+        || claims.get("acr").and_then(Value::as_str).map(|acr| acr.contains("mfa")).unwrap_or(false);
+    context.insert("mfa_authenticated".to_string(), Value::Bool(mfa_authenticated));
+
+    for mapping in claim_map {
+        if let Some(value) = claims.get(&mapping.claim) {
+            context.insert(mapping.context_key.clone(), value.clone());
+        }
+    }
+
+    Value::Object(context)
+}
+
+fn basic_auth_header(client_id: &str, client_secret: &str) -> String {
+    use base64::Engine;
+    let raw = format!("{client_id}:{client_secret}");
+    format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(entries: &[(&str, Value)]) -> HashMap<String, Value> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn scope_projects_to_an_oidc_scope_set() {
+        let claims = claims(&[("scope", Value::String("profile email offline_access".to_string()))]);
+        let context = project_claims(&claims, &[]);
+
+        assert_eq!(
+            context.get("oidc_scope"),
+            Some(&Value::Array(vec![
+                Value::String("profile".to_string()),
+                Value::String("email".to_string()),
+                Value::String("offline_access".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn amr_mfa_sets_mfa_authenticated() {
+        let claims = claims(&[("amr", Value::Array(vec![Value::String("mfa".to_string())]))]);
+        let context = project_claims(&claims, &[]);
+
+        assert_eq!(context.get("mfa_authenticated"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn acr_containing_mfa_sets_mfa_authenticated() {
+        let claims = claims(&[("acr", Value::String("urn:mace:incommon:iap:silver+mfa".to_string()))]);
+        let context = project_claims(&claims, &[]);
+
+        assert_eq!(context.get("mfa_authenticated"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn missing_amr_and_acr_leave_mfa_authenticated_false() {
+        let context = project_claims(&HashMap::new(), &[]);
+        assert_eq!(context.get("mfa_authenticated"), Some(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn custom_claim_map_projects_to_context_key() {
+        let claims = claims(&[("department", Value::String("eng".to_string()))]);
+        let claim_map = vec![ClaimMapping { claim: "department".to_string(), context_key: "team".to_string() }];
+        let context = project_claims(&claims, &claim_map);
+
+        assert_eq!(context.get("team"), Some(&Value::String("eng".to_string())));
+    }
+
+    #[test]
+    fn unmapped_custom_claim_is_absent_from_context() {
+        let claims = claims(&[("department", Value::String("eng".to_string()))]);
+        let claim_map = vec![ClaimMapping { claim: "nonexistent".to_string(), context_key: "team".to_string() }];
+        let context = project_claims(&claims, &claim_map);
+
+        assert_eq!(context.get("team"), None);
+    }
+
+    #[test]
+    fn quote_entity_id_escapes_quotes_and_backslashes() {
+        assert_eq!(quote_entity_id(r#"alice"; forged"#), r#"alice\"; forged"#);
+        assert_eq!(quote_entity_id(r"alice\bob"), r"alice\\bob");
+    }
+}